@@ -23,12 +23,29 @@ enum Command {
     Find {
         #[clap(allow_hyphen_values = true)]
         pattern: Vec<String>,
+        /// Choose among ranked matches with an external fuzzy finder
+        #[clap(short, long)]
+        interactive: bool,
+        /// Skip this path when searching for a match (typically `$PWD`)
+        #[clap(long)]
+        exclude: Option<camino::Utf8PathBuf>,
+        /// Print every matching path, ranked by frecency, instead of just the best one
+        #[clap(long, conflicts_with = "interactive")]
+        list: bool,
+        /// When printing a list, prefix each path with its computed score
+        #[clap(long, requires = "list")]
+        score: bool,
     },
     /// Database manipulation
     Db {
         #[clap(subcommand)]
         cmd: DbCommand,
     },
+    /// Adjusts, sets, or deletes the rank of a single entry
+    Edit {
+        #[clap(subcommand)]
+        op: EditCommand,
+    },
     /// Install shell integrations
     Install {
         #[clap(value_enum)]
@@ -48,7 +65,54 @@ enum DbCommand {
     /// Imports a '.z' file from the user's home directory
     ///
     /// Timestamps are used to resolve conflicting rows
-    Import,
+    ///
+    /// With `--from zoxide`, reads `~/.local/share/zoxide/db.zo` instead
+    Import {
+        /// Format of the database being imported
+        #[clap(long, value_enum, default_value_t = ImportSource::Z)]
+        from: ImportSource,
+    },
+    /// Prunes dead paths and stale, low-rank entries from the database
+    Clean {
+        /// Print what would be removed, without modifying the database
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(ValueEnum, Copy, Clone)]
+enum ImportSource {
+    /// The pipe-delimited '.z' text format (`path|rank|time` per line)
+    Z,
+    /// zoxide's binary 'db.zo' format
+    Zoxide,
+}
+
+/// Below this aged rank, an entry is a candidate for staleness pruning
+const STALE_RANK_FLOOR: f64 = 1.0;
+
+/// Entries untouched for longer than this are candidates for staleness pruning
+const STALE_AGE_SECS: i64 = 90 * 24 * 3600;
+
+#[derive(Subcommand)]
+enum EditCommand {
+    /// Increases a path's rank by `n`
+    Increment {
+        path: camino::Utf8PathBuf,
+        n: f64,
+    },
+    /// Decreases a path's rank by `n`
+    Decrement {
+        path: camino::Utf8PathBuf,
+        n: f64,
+    },
+    /// Sets a path's rank to `score`
+    Set {
+        path: camino::Utf8PathBuf,
+        score: f64,
+    },
+    /// Deletes a path's entry entirely
+    Delete { path: camino::Utf8PathBuf },
 }
 
 #[derive(ValueEnum, Copy, Clone)]
@@ -140,13 +204,55 @@ fn inner(args: &Args) -> anyhow::Result<()> {
             }
         }
         Command::Db {
-            cmd: DbCommand::Import,
+            cmd: DbCommand::Import { from },
         } => {
             let user_dirs = directories::UserDirs::new()
                 .ok_or_else(|| anyhow!("could not get user dirs"))?;
-            let z_path = user_dirs.home_dir().join(".z");
-            let z_text = std::fs::read_to_string(&z_path)
-                .with_context(|| format!("could not read '{z_path:?}'"))?;
+            let rows = match from {
+                ImportSource::Z => {
+                    let z_path = user_dirs.home_dir().join(".z");
+                    let z_text = std::fs::read_to_string(&z_path)
+                        .with_context(|| {
+                            format!("could not read '{z_path:?}'")
+                        })?;
+                    let mut rows = Vec::new();
+                    for line in z_text.lines() {
+                        let mut iter = line.split('|');
+                        let path = iter
+                            .next()
+                            .ok_or_else(|| {
+                                anyhow!("missing path in '{line}'")
+                            })?
+                            .to_lowercase();
+                        let rank: f64 = iter
+                            .next()
+                            .ok_or_else(|| {
+                                anyhow!("missing rank in '{line}'")
+                            })?
+                            .parse()?;
+                        let time: i64 = iter
+                            .next()
+                            .ok_or_else(|| {
+                                anyhow!("missing time in '{line}'")
+                            })?
+                            .parse()?;
+                        rows.push((path, rank, time));
+                    }
+                    rows
+                }
+                ImportSource::Zoxide => {
+                    let zo_path =
+                        user_dirs.home_dir().join(".local/share/zoxide/db.zo");
+                    let bytes = std::fs::read(&zo_path).with_context(|| {
+                        format!("could not read '{zo_path:?}'")
+                    })?;
+                    parse_zoxide_db(&bytes)?
+                }
+            };
+
+            let exclude_patterns = exclude_patterns()?;
+            let home_key = base_dirs.home_dir().to_string_lossy().to_lowercase();
+
             let tx = conn.transaction()?;
             let mut n = 0;
             {
@@ -160,20 +266,10 @@ fn inner(args: &Args) -> anyhow::Result<()> {
                     WHERE excluded.time > zipzap.time;
                     ",
                 )?;
-                for line in z_text.lines() {
-                    let mut iter = line.split('|');
-                    let path = iter
-                        .next()
-                        .ok_or_else(|| anyhow!("missing path in '{line}'"))?
-                        .to_lowercase();
-                    let rank: f64 = iter
-                        .next()
-                        .ok_or_else(|| anyhow!("missing rank in '{line}'"))?
-                        .parse()?;
-                    let time: i64 = iter
-                        .next()
-                        .ok_or_else(|| anyhow!("missing time in '{line}'"))?
-                        .parse()?;
+                for (path, rank, time) in rows {
+                    if is_excluded(&path, &home_key, &exclude_patterns) {
+                        continue;
+                    }
                     stmt.execute(rusqlite::params![path, rank, time])?;
                     n += 1;
                 }
@@ -181,13 +277,104 @@ fn inner(args: &Args) -> anyhow::Result<()> {
             tx.commit()?;
             println!("imported {n} rows");
         }
+        Command::Db {
+            cmd: DbCommand::Clean { dry_run },
+        } => {
+            let mut stmt =
+                conn.prepare("SELECT path, rank, time FROM zipzap")?;
+            let rows = stmt
+                .query_map([], |r| {
+                    Ok((
+                        r.get::<_, String>(0)?,
+                        r.get::<_, f64>(1)?,
+                        r.get::<_, i64>(2)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            drop(stmt);
+
+            let mut dead = Vec::new();
+            let mut stale = Vec::new();
+            for (path, rank, time) in rows {
+                if !case_insensitive_exists(&path) {
+                    dead.push(path);
+                } else if rank * 0.99 < STALE_RANK_FLOOR
+                    && now - time > STALE_AGE_SECS
+                {
+                    stale.push(path);
+                }
+            }
+
+            if *dry_run {
+                for path in dead.iter().chain(stale.iter()) {
+                    println!("would remove '{path}'");
+                }
+            } else {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt =
+                        tx.prepare("DELETE FROM zipzap WHERE path = ?")?;
+                    for path in dead.iter().chain(stale.iter()) {
+                        stmt.execute(rusqlite::params![path])?;
+                    }
+                }
+                tx.commit()?;
+            }
+            let verb = if *dry_run { "would delete" } else { "deleted" };
+            println!("{verb} {} rows", dead.len() + stale.len());
+        }
         Command::Db {
             cmd: DbCommand::Path,
         } => {
             let db_path = camino::Utf8PathBuf::try_from(db_file)?;
             println!("{db_path}");
         }
-        Command::Find { pattern } => {
+        Command::Edit { op } => {
+            let path = match op {
+                EditCommand::Increment { path, .. }
+                | EditCommand::Decrement { path, .. }
+                | EditCommand::Set { path, .. }
+                | EditCommand::Delete { path } => path,
+            };
+            // Fall back to resolving the argument the way a user would type
+            // it (relative to the CWD, with `~` expanded) when the path no
+            // longer exists, so a renamed or deleted directory's entry can
+            // still be fixed up or removed by hand
+            let key = match path.canonicalize_utf8() {
+                Ok(path) => path.as_str().to_lowercase(),
+                Err(_) => {
+                    expand_path(path, &base_dirs)?.as_str().to_lowercase()
+                }
+            };
+            let n = match op {
+                EditCommand::Increment { n, .. } => conn.execute(
+                    "UPDATE zipzap SET rank = rank + :n, time = :now WHERE path = :path",
+                    rusqlite::named_params! {":n": n, ":now": now, ":path": key},
+                )?,
+                EditCommand::Decrement { n, .. } => conn.execute(
+                    "UPDATE zipzap SET rank = rank - :n, time = :now WHERE path = :path",
+                    rusqlite::named_params! {":n": n, ":now": now, ":path": key},
+                )?,
+                EditCommand::Set { score, .. } => conn.execute(
+                    "UPDATE zipzap SET rank = :score, time = :now WHERE path = :path",
+                    rusqlite::named_params! {":score": score, ":now": now, ":path": key},
+                )?,
+                EditCommand::Delete { .. } => conn.execute(
+                    "DELETE FROM zipzap WHERE path = :path",
+                    rusqlite::named_params! {":path": key},
+                )?,
+            };
+            if n == 0 {
+                bail!("no entry found for '{path}'");
+            }
+        }
+        Command::Find {
+            pattern,
+            interactive,
+            exclude,
+            list,
+            score,
+        } => {
             if pattern.is_empty() {
                 return Ok(());
             }
@@ -198,17 +385,93 @@ fn inner(args: &Args) -> anyhow::Result<()> {
                 pat += &p.to_lowercase();
             }
             pat += "%";
-            // Find the best match by "frecency"
-            let path: String = conn.query_one(
-                "
-                SELECT path FROM zipzap WHERE path like ?
-                ORDER BY rank * (3.75/((0.0001 * (? - time) + 1) + 0.25)) DESC
-                LIMIT 1
-                ",
-                rusqlite::params![pat, now],
-                |r| r.get(0),
-            )?;
-            println!("{path}");
+            // Canonicalize the excluded path the same way `Add` does, so it
+            // lines up with the lowercased keys stored in the database
+            let exclude = match exclude {
+                Some(path) => path
+                    .canonicalize_utf8()
+                    .with_context(|| format!("could not find '{path}'"))?
+                    .as_str()
+                    .to_lowercase(),
+                None => String::new(),
+            };
+            if *list {
+                // Print every match, ranked by "frecency", instead of just
+                // the best one
+                let mut stmt = conn.prepare(
+                    "
+                    SELECT path, rank * (3.75/((0.0001 * (:now - time) + 1) + 0.25)) AS score
+                    FROM zipzap WHERE path like :pat AND path != :exclude
+                    ORDER BY score DESC
+                    ",
+                )?;
+                let rows = stmt
+                    .query_map(
+                        rusqlite::named_params! {":pat": pat, ":now": now, ":exclude": exclude},
+                        |r| Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?)),
+                    )?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                for (path, s) in rows {
+                    if *score {
+                        println!("{s:.6} {path}");
+                    } else {
+                        println!("{path}");
+                    }
+                }
+            } else if *interactive {
+                // Fetch every match, ranked by "frecency", and let the user
+                // pick among them with an external fuzzy finder
+                let mut stmt = conn.prepare(
+                    "
+                    SELECT path FROM zipzap WHERE path like :pat AND path != :exclude
+                    ORDER BY rank * (3.75/((0.0001 * (:now - time) + 1) + 0.25)) DESC
+                    ",
+                )?;
+                let paths = stmt
+                    .query_map(
+                        rusqlite::named_params! {":pat": pat, ":now": now, ":exclude": exclude},
+                        |r| r.get::<_, String>(0),
+                    )?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                drop(stmt);
+                if paths.is_empty() {
+                    bail!("no match found");
+                }
+
+                let finder = std::env::var("ZIPZAP_FZF")
+                    .unwrap_or_else(|_| "fzf".to_owned());
+                let mut child = std::process::Command::new(&finder)
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("failed to spawn '{finder}'"))?;
+                let mut stdin =
+                    child.stdin.take().expect("child has piped stdin");
+                for path in &paths {
+                    writeln!(stdin, "{path}")?;
+                }
+                drop(stdin);
+                let output = child.wait_with_output()?;
+                if !output.status.success() {
+                    // User cancelled the fuzzy finder
+                    bail!("no match found");
+                }
+                let chosen = String::from_utf8(output.stdout)
+                    .context("fuzzy finder output was not utf-8")?;
+                print!("{chosen}");
+            } else {
+                // Find the best match by "frecency"
+                let path: String = conn.query_one(
+                    "
+                    SELECT path FROM zipzap WHERE path like :pat AND path != :exclude
+                    ORDER BY rank * (3.75/((0.0001 * (:now - time) + 1) + 0.25)) DESC
+                    LIMIT 1
+                    ",
+                    rusqlite::named_params! {":pat": pat, ":now": now, ":exclude": exclude},
+                    |r| r.get(0),
+                )?;
+                println!("{path}");
+            }
         }
         Command::Install { shell } => {
             let shell = match shell {
@@ -286,6 +549,143 @@ fn inner(args: &Args) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Resolves `path` the way a user would type it on the command line: `~`
+/// is expanded to the home directory, and relative paths are resolved
+/// against the current directory. Unlike `canonicalize_utf8`, this does not
+/// require the path to exist.
+fn expand_path(
+    path: &camino::Utf8Path,
+    base_dirs: &directories::BaseDirs,
+) -> anyhow::Result<camino::Utf8PathBuf> {
+    let home = camino::Utf8Path::from_path(base_dirs.home_dir())
+        .ok_or_else(|| anyhow!("home directory is not utf-8"))?;
+    let path = if path.as_str() == "~" {
+        home.to_path_buf()
+    } else if let Some(rest) = path.as_str().strip_prefix("~/") {
+        home.join(rest)
+    } else {
+        path.to_path_buf()
+    };
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        let cwd = std::env::current_dir()
+            .context("could not get current directory")?;
+        let cwd = camino::Utf8PathBuf::try_from(cwd).map_err(|e| {
+            anyhow!("current directory is not utf-8: {e}")
+        })?;
+        Ok(cwd.join(path))
+    }
+}
+
+/// Checks whether `path` exists on disk, resolving each component
+/// case-insensitively
+///
+/// Stored keys are always lowercased (see `Add`), so a direct
+/// `symlink_metadata` call would report a live, mixed-case directory as
+/// missing on any case-sensitive filesystem. Most rows are either already
+/// all-lowercase or sit on a case-insensitive filesystem, so we try the
+/// cheap direct check first and only fall back to the O(depth) per-component
+/// walk when it fails.
+fn case_insensitive_exists(path: &str) -> bool {
+    if std::fs::symlink_metadata(path).is_ok() {
+        return true;
+    }
+
+    let mut current = std::path::PathBuf::new();
+    for component in std::path::Path::new(path).components() {
+        match component {
+            std::path::Component::Normal(name) => {
+                let name = name.to_string_lossy().to_lowercase();
+                let Ok(entries) = std::fs::read_dir(&current) else {
+                    return false;
+                };
+                let found = entries.filter_map(Result::ok).find(|e| {
+                    e.file_name().to_string_lossy().to_lowercase() == name
+                });
+                match found {
+                    Some(entry) => current = entry.path(),
+                    None => return false,
+                }
+            }
+            other => current.push(other.as_os_str()),
+        }
+    }
+    std::fs::symlink_metadata(&current).is_ok()
+}
+
+/// Parses zoxide's `db.zo` format
+///
+/// `db.zo` is a bincode-encoded `Store { dirs: Vec<Dir> }`; bincode encodes
+/// a `Vec<T>` as a leading little-endian `u64` element count followed by
+/// that many elements, and each `Dir` is a length-prefixed UTF-8 path, an
+/// `f64` rank, and an `i64` epoch timestamp, packed back-to-back
+fn parse_zoxide_db(bytes: &[u8]) -> anyhow::Result<Vec<(String, f64, i64)>> {
+    let (count, mut cursor) = take::<8>(bytes)?;
+    let count = u64::from_le_bytes(count);
+
+    let mut rows = Vec::new();
+    for _ in 0..count {
+        let (len, rest) = take::<8>(cursor)?;
+        let len = u64::from_le_bytes(len) as usize;
+        let path_bytes = rest
+            .get(..len)
+            .ok_or_else(|| anyhow!("truncated path in db.zo"))?;
+        let path = std::str::from_utf8(path_bytes)
+            .context("path in db.zo is not utf-8")?
+            .to_lowercase();
+
+        let (rank, rest) = take::<8>(&rest[len..])?;
+        let rank = f64::from_le_bytes(rank);
+        let (time, rest) = take::<8>(rest)?;
+        let time = i64::from_le_bytes(time);
+
+        rows.push((path, rank, time));
+        cursor = rest;
+    }
+    Ok(rows)
+}
+
+/// Splits the first `N` bytes off of `bytes`, returning them along with the
+/// remaining slice
+fn take<const N: usize>(bytes: &[u8]) -> anyhow::Result<([u8; N], &[u8])> {
+    if bytes.len() < N {
+        bail!("truncated record in db.zo");
+    }
+    let (head, tail) = bytes.split_at(N);
+    Ok((head.try_into().unwrap(), tail))
+}
+
+/// Parses the `ZIPZAP_EXCLUDE` environment variable into a list of glob
+/// patterns, mirroring zoxide's `$_ZO_EXCLUDE_DIRS`
+///
+/// Patterns are lowercased before compiling, since `is_excluded` always
+/// matches them against the lowercased keys stored in the database
+fn exclude_patterns() -> anyhow::Result<Vec<glob::Pattern>> {
+    match std::env::var("ZIPZAP_EXCLUDE") {
+        Ok(s) => s
+            .split(':')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                glob::Pattern::new(&p.to_lowercase()).with_context(|| {
+                    format!("invalid glob '{p}' in ZIPZAP_EXCLUDE")
+                })
+            })
+            .collect(),
+        Err(std::env::VarError::NotPresent) => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns `true` if `path` should be skipped during import: it's the home
+/// or root directory (the implicit rules `Add` already applies), or it
+/// matches one of `patterns`
+fn is_excluded(path: &str, home: &str, patterns: &[glob::Pattern]) -> bool {
+    path == home
+        || std::path::Path::new(path).components().count() == 1
+        || patterns.iter().any(|p| p.matches(path))
+}
+
 /// Sends a question to the user, expecting a `[y,n]` reply
 fn read_yn(prompt: &str) -> std::io::Result<bool> {
     loop {
@@ -361,3 +761,49 @@ fn copy_check(path: std::path::PathBuf, text: &str) -> anyhow::Result<bool> {
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_zoxide_db;
+
+    /// Builds a sample `db.zo` byte sequence matching bincode's encoding of
+    /// `Store { dirs: Vec<Dir> }`: a `u64` element count, then per-element a
+    /// `u64` path length, the path bytes, an `f64` rank, and an `i64` epoch
+    fn sample_db(dirs: &[(&str, f64, i64)]) -> Vec<u8> {
+        let mut bytes = (dirs.len() as u64).to_le_bytes().to_vec();
+        for (path, rank, time) in dirs {
+            bytes.extend((path.len() as u64).to_le_bytes());
+            bytes.extend(path.as_bytes());
+            bytes.extend(rank.to_le_bytes());
+            bytes.extend(time.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_sample_zoxide_db() {
+        let bytes = sample_db(&[
+            ("/home/user/Projects/MyApp", 12.5, 1_700_000_000),
+            ("/home/user", 3.0, 1_690_000_000),
+        ]);
+        let rows = parse_zoxide_db(&bytes).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "/home/user/projects/myapp".to_owned(),
+                    12.5,
+                    1_700_000_000
+                ),
+                ("/home/user".to_owned(), 3.0, 1_690_000_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_db() {
+        let mut bytes = sample_db(&[("/home/user", 1.0, 0)]);
+        bytes.truncate(bytes.len() - 4);
+        assert!(parse_zoxide_db(&bytes).is_err());
+    }
+}